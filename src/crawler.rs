@@ -3,13 +3,91 @@
 use async_stream::stream;
 use futures::stream::FuturesUnordered;
 use futures::{Stream, StreamExt};
-use reqwest;
+use regex::RegexSet;
+use reqwest::{Client, Url};
 use scraper::{Html, Selector};
-use std::collections::HashSet;
+use std::collections::{HashSet, VecDeque};
 use std::sync::Arc;
-use reqwest::Url;
+use std::time::Duration;
 use tokio::process::Command;
 use tokio::sync::mpsc::UnboundedReceiver;
+use tokio_util::sync::CancellationToken;
+
+use crate::politeness::Politeness;
+
+/// Tunables for the shared `reqwest::Client` used across a crawl.
+///
+/// Building one `Client` and reusing it lets `reqwest` pool and reuse
+/// connections across the thousands of fetches a crawl can produce, instead
+/// of paying TCP+TLS setup on every request.
+#[derive(Clone, Debug)]
+pub struct CrawlerConfig {
+    pub pool_max_idle_per_host: usize,
+    pub pool_idle_timeout: Duration,
+    pub user_agent: String,
+}
+
+impl Default for CrawlerConfig {
+    fn default() -> Self {
+        Self {
+            pool_max_idle_per_host: 10,
+            pool_idle_timeout: Duration::from_secs(90),
+            user_agent: format!("scraper_iter/{}", env!("CARGO_PKG_VERSION")),
+        }
+    }
+}
+
+impl CrawlerConfig {
+    /// Builds the shared `Client` described by this config.
+    pub fn build_client(&self) -> Client {
+        Client::builder()
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .user_agent(self.user_agent.clone())
+            .build()
+            .expect("Failed to build HTTP client")
+    }
+}
+
+/// Controls how persistently `fetch_url` retries a failing request.
+#[derive(Clone, Copy, Debug)]
+pub struct FetchPolicy {
+    pub request_timeout: Duration,
+    pub retries: u32,
+}
+
+impl Default for FetchPolicy {
+    fn default() -> Self {
+        Self {
+            request_timeout: Duration::from_secs(30),
+            retries: 3,
+        }
+    }
+}
+
+/// Outcome of attempting to fetch and parse a single URL, distinguishing a
+/// successful fetch from one that permanently failed after all retries.
+enum FetchOutcome {
+    Fetched(Vec<String>),
+    Failed(String),
+    /// Disallowed by robots.txt; never actually fetched.
+    Skipped,
+}
+
+/// Returns whether `url`'s host is permitted by `allowed_hosts`.
+///
+/// `None` means every host is allowed; a URL that fails to parse or has no
+/// host is rejected once an allow-list is in effect.
+fn host_allowed(url: &str, allowed_hosts: &Option<Arc<HashSet<String>>>) -> bool {
+    match allowed_hosts {
+        None => true,
+        Some(hosts) => Url::parse(url)
+            .ok()
+            .and_then(|parsed| parsed.host_str().map(|host| host.to_string()))
+            .map(|host| hosts.contains(&host))
+            .unwrap_or(false),
+    }
+}
 
 /// Crawls URLs provided via a receiver and returns an asynchronous stream of found URLs.
 /// It allows filtering of URLs, and only if a URL is not filtered out, its children are crawled.
@@ -17,54 +95,133 @@ use tokio::sync::mpsc::UnboundedReceiver;
 /// # Arguments
 ///
 /// * `url_receiver` - An UnboundedReceiver that provides URLs to start crawling from.
-/// * `filter` - A function that takes a URL and returns a boolean indicating whether to proceed with its children.
+/// * `include_patterns` - A URL's children are only crawled if at least one of these matches.
+/// * `exclude_patterns` - A URL's children are never crawled if any of these match.
+/// * `client` - A shared, pre-configured `Client` used for every fetch so connections are pooled.
+/// * `max_concurrency` - The maximum number of fetches allowed in flight at once.
+/// * `cancel` - Token used to request a graceful shutdown of the crawl.
+/// * `shutdown_grace_period` - How long to let in-flight fetches finish after cancellation before dropping them.
+/// * `fetch_policy` - Per-request timeout and retry/backoff settings.
+/// * `error_sender` - Optional sink for URLs that permanently failed to fetch; falls back to stderr if `None`.
+/// * `max_depth` - Maximum link depth (0 = only the seed URLs) to follow children to.
+/// * `allowed_hosts` - If set, children whose host isn't in this set are dropped before being queued.
+/// * `politeness` - robots.txt compliance and per-host rate limiting, shared across the crawl.
 ///
 /// # Returns
 ///
 /// An asynchronous stream of URLs as they are found.
-pub fn crawl_urls<F>(
-    mut url_receiver: UnboundedReceiver<String>,
-    filter: F,
-) -> impl Stream<Item = String>
-where
-    F: Fn(&String) -> bool + Send + Sync + 'static,
-{
-    let filter = Arc::new(filter);
+pub fn crawl_urls(
+    mut url_receiver: UnboundedReceiver<(String, usize)>,
+    include_patterns: RegexSet,
+    exclude_patterns: RegexSet,
+    client: Arc<Client>,
+    max_concurrency: usize,
+    cancel: CancellationToken,
+    shutdown_grace_period: Duration,
+    fetch_policy: FetchPolicy,
+    error_sender: Option<tokio::sync::mpsc::UnboundedSender<String>>,
+    max_depth: usize,
+    allowed_hosts: Option<Arc<HashSet<String>>>,
+    politeness: Arc<Politeness>,
+) -> impl Stream<Item = String> {
     let crawled_urls = Arc::new(tokio::sync::Mutex::new(HashSet::new())); // Shared set of visited URLs
 
     Box::pin(stream! {
         let mut to_crawl = FuturesUnordered::new();
+        // URLs that are ready to crawl but are held back until an in-flight slot frees up
+        let mut pending_queue: VecDeque<(String, usize)> = VecDeque::new();
 
         loop {
             tokio::select! {
+                // Cancellation requested: stop accepting new URLs, let in-flight
+                // fetches finish (bounded by the grace period), flush what they
+                // yield, then stop.
+                _ = cancel.cancelled() => {
+                    let mut drained = Vec::new();
+                    let _ = tokio::time::timeout(shutdown_grace_period, async {
+                        while let Some((url, _depth, outcome)) = to_crawl.next().await {
+                            // Robots.txt-disallowed URLs are dropped here as well.
+                            if !matches!(outcome, FetchOutcome::Skipped) {
+                                drained.push(url);
+                            }
+                        }
+                    }).await;
+
+                    for url in drained {
+                        yield url;
+                    }
+                    break;
+                },
                 // Receive new URLs to crawl
-                Some(url) = url_receiver.recv() => {
+                Some((url, depth)) = url_receiver.recv() => {
                     let mut visited = crawled_urls.lock().await;
                     if !visited.contains(&url) {
                         visited.insert(url.clone());
                         drop(visited); // Release the lock before awaiting
 
-                        // Start fetching the URL
-                        to_crawl.push(fetch_url(url));
+                        if to_crawl.len() < max_concurrency {
+                            to_crawl.push(fetch_url(client.clone(), url, depth, fetch_policy, politeness.clone()));
+                        } else {
+                            pending_queue.push_back((url, depth));
+                        }
                     }
                 },
                 // Process the next crawled URL
-                Some((url, child_urls)) = to_crawl.next() => {
-                    // Yield the current URL
-                    yield url.clone();
+                Some((url, depth, outcome)) = to_crawl.next() => {
+                    // Robots.txt-disallowed URLs were never actually fetched, so
+                    // they're silently dropped rather than yielded or logged.
+                    let child_urls = match outcome {
+                        FetchOutcome::Skipped => {
+                            Vec::new()
+                        }
+                        FetchOutcome::Fetched(child_urls) => {
+                            yield url.clone();
+                            child_urls
+                        }
+                        FetchOutcome::Failed(reason) => {
+                            yield url.clone();
+                            let message = format!("{}\t{}", url, reason);
+                            match &error_sender {
+                                Some(sender) => {
+                                    let _ = sender.send(message);
+                                }
+                                None => eprintln!("{}", message),
+                            }
+                            Vec::new()
+                        }
+                    };
+
+                    // Decide whether to proceed with the children: at least one include
+                    // pattern must match, no exclude pattern may match, and we must be
+                    // within the configured depth limit.
+                    let in_scope = include_patterns.is_match(&url) && !exclude_patterns.is_match(&url);
+                    if in_scope && depth < max_depth {
+                        // Schedule the child URLs to be crawled
+                        for child_url in child_urls {
+                            if !host_allowed(&child_url, &allowed_hosts) {
+                                continue;
+                            }
 
-                    // Decide whether to proceed with the children based on the filter
-                    if !filter(&url) {
-                        continue;
+                            let mut visited = crawled_urls.lock().await;
+                            if !visited.contains(&child_url) {
+                                visited.insert(child_url.clone());
+                                drop(visited); // Release the lock before awaiting
+
+                                let child_depth = depth + 1;
+                                if to_crawl.len() < max_concurrency {
+                                    to_crawl.push(fetch_url(client.clone(), child_url, child_depth, fetch_policy, politeness.clone()));
+                                } else {
+                                    pending_queue.push_back((child_url, child_depth));
+                                }
+                            }
+                        }
                     }
 
-                    // Schedule the child URLs to be crawled
-                    for child_url in child_urls {
-                        let mut visited = crawled_urls.lock().await;
-                        if !visited.contains(&child_url) {
-                            visited.insert(child_url.clone());
-                            drop(visited); // Release the lock before awaiting
-                            to_crawl.push(fetch_url(child_url));
+                    // A slot just freed up; backfill from the pending queue
+                    while to_crawl.len() < max_concurrency {
+                        match pending_queue.pop_front() {
+                            Some((next_url, next_depth)) => to_crawl.push(fetch_url(client.clone(), next_url, next_depth, fetch_policy, politeness.clone())),
+                            None => break,
                         }
                     }
                 },
@@ -77,35 +234,78 @@ where
     })
 }
 
-/// Fetches the content of a URL and extracts child URLs.
+/// Fetches the content of a URL and extracts child URLs, retrying on
+/// timeout, connection error, or 5xx responses with exponential backoff.
 ///
 /// # Arguments
 ///
+/// * `client` - The shared, pooled `Client` to fetch with.
 /// * `url` - The URL to fetch and parse.
+/// * `depth` - The link depth this URL was discovered at, passed through unchanged.
+/// * `policy` - Per-request timeout and retry/backoff settings.
+/// * `politeness` - Checks robots.txt and enforces per-host request spacing.
+///   Both are done here, inside the fetch future, rather than in the stream's
+///   driver loop, so a slow robots.txt lookup for one host never blocks the
+///   rest of the crawl from making progress.
 ///
 /// # Returns
 ///
-/// A tuple containing the original URL and a vector of child URLs found on the page.
-async fn fetch_url(url: String) -> (String, Vec<String>) {
+/// A tuple containing the original URL, its depth, and the outcome of fetching it.
+async fn fetch_url(
+    client: Arc<Client>,
+    url: String,
+    depth: usize,
+    policy: FetchPolicy,
+    politeness: Arc<Politeness>,
+) -> (String, usize, FetchOutcome) {
+    if !politeness.is_allowed(&url).await {
+        return (url, depth, FetchOutcome::Skipped);
+    }
 
-    // Attempt to fetch the URL content
-    let body = match reqwest::get(&url).await {
-        Ok(resp) => match resp.text().await {
-            Ok(body) => body,
-            Err(_) => return (url, vec![]),
-        },
-        Err(_) => return (url, vec![]),
-    };
+    let mut last_error = String::new();
+
+    for attempt in 0..=policy.retries {
+        politeness.wait_turn(&url).await;
+
+        match tokio::time::timeout(policy.request_timeout, client.get(&url).send()).await {
+            Ok(Ok(resp)) if resp.status().is_server_error() => {
+                last_error = format!("server error: {}", resp.status());
+            }
+            Ok(Ok(resp)) => match resp.text().await {
+                Ok(body) => {
+                    return (
+                        url.clone(),
+                        depth,
+                        FetchOutcome::Fetched(extract_child_urls(&body, &url).await),
+                    )
+                }
+                Err(err) => last_error = format!("error reading body: {}", err),
+            },
+            Ok(Err(err)) => last_error = format!("request error: {}", err),
+            Err(_) => last_error = format!("timed out after {:?}", policy.request_timeout),
+        }
+
+        if attempt < policy.retries {
+            // Cap the exponent so a large --retries can't overflow the shift.
+            let backoff = Duration::from_millis(200u64.saturating_mul(1u64 << attempt.min(20)));
+            tokio::time::sleep(backoff).await;
+        }
+    }
+
+    (url, depth, FetchOutcome::Failed(last_error))
+}
 
-    // Parse the content to find child links
-    let document = Html::parse_document(&body);
+/// Parses a fetched page's body and resolves every link on it to an absolute
+/// child URL, relative to both the page itself and its parent directory.
+async fn extract_child_urls(body: &str, url: &str) -> Vec<String> {
+    let document = Html::parse_document(body);
     let selector = Selector::parse("a[href]").unwrap();
     let mut child_urls = Vec::new();
 
     for element in document.select(&selector) {
         if let Some(href) = element.value().attr("href") {
-            let child_url = resolve_url(href, &url).await;
-            let other_url = url.clone();
+            let child_url = resolve_url(href, url).await;
+            let other_url = url.to_string();
             let mut other_url = Url::parse(&other_url).expect("URL should be valid");
             {
                 let mut segs = other_url.path_segments_mut().unwrap();
@@ -118,7 +318,7 @@ async fn fetch_url(url: String) -> (String, Vec<String>) {
         }
     }
 
-    (url, child_urls)
+    child_urls
 }
 
 /// Resolves relative URLs to absolute URLs based on the base URL
@@ -176,13 +376,13 @@ async fn resolve_url(href: &str, base_url: &str) -> String {
 ///
 /// # Arguments
 ///
+/// * `client` - The shared, pooled `Client` to check with.
 /// * `url` - The URL to check.
 ///
 /// # Returns
 ///
 /// A boolean indicating whether the URL is valid or not.
-async fn is_valid_url(url: &str) -> bool {
-    let client = reqwest::Client::new();
+async fn is_valid_url(client: &Client, url: &str) -> bool {
     match client.head(url).send().await {
         Ok(response) => response.status().is_success(),
         Err(_) => false,
@@ -194,15 +394,16 @@ async fn is_valid_url(url: &str) -> bool {
 ///
 /// # Arguments
 ///
+/// * `client` - The shared, pooled `Client` used to validate the URL before wgetting.
 /// * `url` - The link to fetch
 ///
 /// # Returns
 ///
 /// Result<(), Box<dyn std::error::Error>>
-pub(crate) async fn wget(url: &str) -> Result<(), Box<dyn std::error::Error>> {
+pub(crate) async fn wget(client: &Client, url: &str) -> Result<(), Box<dyn std::error::Error>> {
     use tokio::process::Command;
 
-    if !is_valid_url(url).await {
+    if !is_valid_url(client, url).await {
         return Err("Invalid URL".into());
     }
 
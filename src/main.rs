@@ -6,6 +6,7 @@ use tokio::sync::mpsc;
 use tokio::io::{AsyncWriteExt, BufWriter};
 
 mod crawler;
+mod politeness;
 mod search; // New module for search functionality
 
 /// Simple web crawler to find URLs starting from initial links or search results.
@@ -20,9 +21,14 @@ struct Args {
     #[arg(short, long)]
     input_file: Option<String>,
 
-    /// Filter pattern to decide whether to proceed with a URL's children, eahc pattern is ORed together
-    #[arg(short, long)]
-    filter_pattern: Vec<String>,
+    /// Regex pattern; a URL's children are only crawled if at least one include pattern
+    /// matches (repeatable, ORed together). With none given, nothing is crawled past the seeds.
+    #[arg(long)]
+    include_pattern: Vec<String>,
+
+    /// Regex pattern; a URL's children are never crawled if any exclude pattern matches (repeatable)
+    #[arg(long)]
+    exclude_pattern: Vec<String>,
 
     /// Site to perform site search and add URLs from
     #[arg(long)]
@@ -32,13 +38,78 @@ struct Args {
     #[arg(long, default_value_t = 10)]
     search_limit: u32,
 
+    /// Search engine(s) to query when --search-site is given (repeatable).
+    /// Supported: google, bing, duckduckgo, brave. Defaults to google.
+    #[arg(long)]
+    search_engine: Vec<String>,
+
     /// If given, output links will be output to given file
     #[arg(long)]
     output_path: Option<String>,
 
     /// If given the found links will be downloaded with wget
     #[arg(long)]
-    wget: bool
+    wget: bool,
+
+    /// Maximum idle connections to keep open per host in the shared connection pool
+    #[arg(long, default_value_t = 10)]
+    pool_max_idle_per_host: usize,
+
+    /// How long (in seconds) an idle pooled connection is kept open before being closed
+    #[arg(long, default_value_t = 90)]
+    pool_idle_timeout: u64,
+
+    /// User-Agent header sent with every request
+    #[arg(long, default_value_t = format!("scraper_iter/{}", env!("CARGO_PKG_VERSION")))]
+    user_agent: String,
+
+    /// Maximum number of fetches allowed in flight at once
+    #[arg(long, default_value_t = 50, value_parser = clap::value_parser!(usize).range(1..))]
+    max_concurrency: usize,
+
+    /// Stop the crawl after this many seconds, flushing any in-flight results
+    #[arg(long)]
+    max_duration: Option<u64>,
+
+    /// Stop the crawl after this many URLs have been yielded
+    #[arg(long)]
+    max_urls: Option<usize>,
+
+    /// How long (in seconds) to let in-flight fetches finish after a shutdown is triggered
+    #[arg(long, default_value_t = 10)]
+    shutdown_grace_period: u64,
+
+    /// How long (in seconds) to wait for a single request before treating it as failed
+    #[arg(long, default_value_t = 30)]
+    request_timeout: u64,
+
+    /// How many times to retry a failed request before giving up on it
+    #[arg(long, default_value_t = 3)]
+    retries: u32,
+
+    /// If given, URLs that permanently failed to fetch are written to this file (created or truncated at startup) instead of stderr
+    #[arg(long)]
+    error_log: Option<String>,
+
+    /// Maximum link depth to follow from the seed URLs (0 = only the seeds themselves)
+    #[arg(long)]
+    max_depth: Option<usize>,
+
+    /// Restrict crawled children to the same host(s) as the initial seed URLs
+    #[arg(long)]
+    same_host: bool,
+
+    /// Restrict crawled children to this host (repeatable); combines with --same-host
+    #[arg(long)]
+    allowed_host: Vec<String>,
+
+    /// Minimum delay (in seconds) enforced between requests to the same host
+    #[arg(long, default_value_t = 0.0)]
+    rate_limit: f64,
+
+    /// Skip fetching and honoring each host's robots.txt
+    #[arg(long)]
+    ignore_robots: bool,
 }
 
 #[tokio::main]
@@ -63,34 +134,74 @@ async fn main() {
         println!("No initial URLs provided. Use --url, --input-file, or --search-site to specify starting URLs.");
         return;
     }
-    // Define the filter function based on the filter pattern
-    let filter_patterns: Vec<String> = args.filter_pattern.clone();
+    // Compile the include/exclude patterns once; a URL's children are only crawled
+    // if at least one include pattern matches and no exclude pattern matches.
+    let include_patterns = regex::RegexSet::new(&args.include_pattern).unwrap_or_else(|err| {
+        eprintln!("Invalid include pattern: {}", err);
+        std::process::exit(1);
+    });
+    let exclude_patterns = regex::RegexSet::new(&args.exclude_pattern).unwrap_or_else(|err| {
+        eprintln!("Invalid exclude pattern: {}", err);
+        std::process::exit(1);
+    });
 
-    let filter = move |url: &String| {
-        filter_patterns.iter().any(|pattern| url.contains(pattern))
+    // Build the shared, pooled HTTP client used for every fetch in this crawl
+    let crawler_config = crawler::CrawlerConfig {
+        pool_max_idle_per_host: args.pool_max_idle_per_host,
+        pool_idle_timeout: std::time::Duration::from_secs(args.pool_idle_timeout),
+        user_agent: args.user_agent.clone(),
     };
+    let client = std::sync::Arc::new(crawler_config.build_client());
 
-    // Create a channel for dynamically added URLs
-    let (url_sender, url_receiver) = mpsc::unbounded_channel::<String>();
+    // Build the same-host/allowed-host scope before URLs start flowing, since
+    // --same-host is derived from the initial seed URLs.
+    let mut allowed_hosts_set: std::collections::HashSet<String> =
+        args.allowed_host.iter().cloned().collect();
+    if args.same_host {
+        for url in &initial_urls {
+            if let Ok(parsed) = reqwest::Url::parse(url) {
+                if let Some(host) = parsed.host_str() {
+                    allowed_hosts_set.insert(host.to_string());
+                }
+            }
+        }
+    }
+    let allowed_hosts = if allowed_hosts_set.is_empty() {
+        None
+    } else {
+        Some(std::sync::Arc::new(allowed_hosts_set))
+    };
+    let max_depth = args.max_depth.unwrap_or(usize::MAX);
+
+    // Create a channel for dynamically added URLs, tagged with their crawl depth
+    let (url_sender, url_receiver) = mpsc::unbounded_channel::<(String, usize)>();
 
     // If search_site is specified, start the search task
     if let Some(search_site) = args.search_site.clone() {
         // Clone the sender to move into the async task
         let sender_clone = url_sender.clone();
         let search_limit = args.search_limit;
+        let engines: Vec<_> = if args.search_engine.is_empty() {
+            vec![search::engine_from_name("google")]
+        } else {
+            args.search_engine
+                .iter()
+                .map(|name| search::engine_from_name(name))
+                .collect()
+        };
         tokio::spawn(async move {
             // Perform the site-specific search
             if let Err(err) =
-                search::search_site_urls(&search_site, search_limit, sender_clone).await
+                search::search_site_urls(engines, &search_site, search_limit, sender_clone).await
             {
                 eprintln!("Error during site search: {}", err);
             }
         });
     }
-    // Send initial URLs into the sender
+    // Send initial URLs into the sender, seeded at depth 0
     for url in initial_urls {
         url_sender
-            .send(url)
+            .send((url, 0))
             .unwrap_or_else(|err| eprintln!("Error sending initial URL: {}", err));
     }
     // Open the output file if specified
@@ -107,18 +218,88 @@ async fn main() {
         }
     };
 
+    // Cancellation token used to trigger a graceful shutdown, either from a
+    // --max-duration timer or once --max-urls have been yielded.
+    let cancel = tokio_util::sync::CancellationToken::new();
+    if let Some(max_duration) = args.max_duration {
+        let cancel_for_timer = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_secs(max_duration)).await;
+            cancel_for_timer.cancel();
+        });
+    }
+
+    // Wire up permanently-failed URLs to --error-log, or let the crawler print them to stderr
+    let error_sender = match &args.error_log {
+        None => None,
+        Some(path) => {
+            let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+            let mut error_file = match tokio::fs::File::create(path).await {
+                Ok(f) => BufWriter::new(f),
+                Err(err) => {
+                    eprintln!("Error creating error log file: {}", err);
+                    return;
+                }
+            };
+            tokio::spawn(async move {
+                while let Some(line) = rx.recv().await {
+                    error_file.write_all(line.as_bytes()).await.unwrap();
+                    error_file.write_all(b"\n").await.unwrap();
+                }
+                error_file.flush().await.unwrap();
+            });
+            Some(tx)
+        }
+    };
+
+    let fetch_policy = crawler::FetchPolicy {
+        request_timeout: std::time::Duration::from_secs(args.request_timeout),
+        retries: args.retries,
+    };
+
+    let politeness = std::sync::Arc::new(politeness::Politeness::new(
+        client.clone(),
+        args.user_agent.clone(),
+        std::time::Duration::from_secs_f64(args.rate_limit),
+        args.ignore_robots,
+    ));
+
     // Start crawling and get the stream of URLs
-    let mut stream = crawler::crawl_urls(url_receiver, filter.clone());
+    let mut stream = crawler::crawl_urls(
+        url_receiver,
+        include_patterns.clone(),
+        exclude_patterns.clone(),
+        client.clone(),
+        args.max_concurrency,
+        cancel.clone(),
+        std::time::Duration::from_secs(args.shutdown_grace_period),
+        fetch_policy,
+        error_sender,
+        max_depth,
+        allowed_hosts,
+        politeness,
+    );
+
+    let mut urls_yielded: usize = 0;
 
     // Process the stream of URLs
     while let Some(url) = stream.next().await {
+        urls_yielded += 1;
+        if let Some(max_urls) = args.max_urls {
+            if urls_yielded >= max_urls {
+                cancel.cancel();
+            }
+        }
+
         // Clone the URL for the async task
         let url_for_task = url.clone();
 
         // wgetting
-        if args.wget && filter(&url_for_task) {
+        let in_scope = include_patterns.is_match(&url_for_task) && !exclude_patterns.is_match(&url_for_task);
+        if args.wget && in_scope {
+            let client_for_task = client.clone();
             tokio::spawn(async move {
-                if let Err(err) = crawler::wget(&url_for_task).await {
+                if let Err(err) = crawler::wget(&client_for_task, &url_for_task).await {
                     eprintln!("Error wgetting: {}", err);
                 }
             });
@@ -0,0 +1,192 @@
+// src/politeness.rs
+
+use reqwest::{Client, Url};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// A host's robots.txt rules, scoped to the entries that apply to us
+/// (either a group whose agent name appears in our user agent or the `*`
+/// fallback group).
+#[derive(Clone, Debug, Default)]
+struct RobotsRules {
+    disallow: Vec<String>,
+    crawl_delay: Option<Duration>,
+}
+
+impl RobotsRules {
+    /// Parses a robots.txt body, applying only the single best-matching
+    /// group's directives: an exact/substring match on `user_agent` wins
+    /// over the `*` fallback group, rather than merging both together.
+    fn parse(body: &str, user_agent: &str) -> Self {
+        // Split the body into groups, each starting at its `User-agent` line(s)
+        // and running until the next group starts.
+        let mut groups: Vec<(Vec<String>, Vec<(String, String)>)> = Vec::new();
+
+        for raw_line in body.lines() {
+            let line = raw_line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let key = key.trim().to_ascii_lowercase();
+            let value = value.trim().to_string();
+
+            if key == "user-agent" {
+                // A `User-agent` line starts a new group unless it directly
+                // follows another `User-agent` line, in which case it joins
+                // the group just started (multiple agents, one rule set).
+                match groups.last_mut() {
+                    Some((agents, directives)) if directives.is_empty() => agents.push(value),
+                    _ => groups.push((vec![value], Vec::new())),
+                }
+            } else if let Some((_, directives)) = groups.last_mut() {
+                directives.push((key, value));
+            }
+        }
+
+        // Prefer the first group that names our user agent; fall
+        // back to the `*` group; otherwise no directives apply to us.
+        let best_group = groups
+            .iter()
+            .find(|(agents, _)| {
+                agents
+                    .iter()
+                    .any(|agent| agent != "*" && user_agent.to_ascii_lowercase().contains(&agent.to_ascii_lowercase()))
+            })
+            .or_else(|| groups.iter().find(|(agents, _)| agents.iter().any(|agent| agent == "*")));
+
+        let mut rules = RobotsRules::default();
+        if let Some((_, directives)) = best_group {
+            for (key, value) in directives {
+                match key.as_str() {
+                    "disallow" if !value.is_empty() => rules.disallow.push(value.clone()),
+                    "crawl-delay" => {
+                        if let Ok(secs) = value.parse::<f64>() {
+                            rules.crawl_delay = Some(Duration::from_secs_f64(secs));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        rules
+    }
+
+    /// Whether `path` is allowed under these rules (simple prefix matching).
+    fn allows(&self, path: &str) -> bool {
+        !self.disallow.iter().any(|prefix| path.starts_with(prefix.as_str()))
+    }
+}
+
+/// Per-host politeness: caches each host's robots.txt rules and enforces a
+/// minimum delay between requests to the same host.
+///
+/// Shared across a crawl behind an `Arc`, so every in-flight fetch consults
+/// and updates the same cache and rate limiter.
+pub struct Politeness {
+    client: Arc<Client>,
+    user_agent: String,
+    rate_limit: Duration,
+    ignore_robots: bool,
+    rules: Mutex<HashMap<String, RobotsRules>>,
+    last_request_at: Mutex<HashMap<String, Instant>>,
+}
+
+impl Politeness {
+    /// * `rate_limit` - Minimum delay enforced between requests to the same host,
+    ///   regardless of what robots.txt asks for.
+    /// * `ignore_robots` - If true, robots.txt is never fetched or consulted.
+    pub fn new(client: Arc<Client>, user_agent: String, rate_limit: Duration, ignore_robots: bool) -> Self {
+        Self {
+            client,
+            user_agent,
+            rate_limit,
+            ignore_robots,
+            rules: Mutex::new(HashMap::new()),
+            last_request_at: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Fetches and caches (per host) the robots.txt rules for `url`'s origin.
+    async fn rules_for(&self, url: &Url) -> RobotsRules {
+        let host = url.host_str().unwrap_or("").to_string();
+
+        {
+            let cache = self.rules.lock().await;
+            if let Some(rules) = cache.get(&host) {
+                return rules.clone();
+            }
+        }
+
+        let robots_url = match url.port() {
+            Some(port) => format!("{}://{}:{}/robots.txt", url.scheme(), host, port),
+            None => format!("{}://{}/robots.txt", url.scheme(), host),
+        };
+        let rules = match self.client.get(&robots_url).send().await {
+            Ok(resp) => match resp.text().await {
+                Ok(body) => RobotsRules::parse(&body, &self.user_agent),
+                Err(_) => RobotsRules::default(),
+            },
+            Err(_) => RobotsRules::default(),
+        };
+
+        self.rules.lock().await.insert(host, rules.clone());
+        rules
+    }
+
+    /// Returns whether we're allowed to fetch `url` per its host's robots.txt.
+    /// Always true when `ignore_robots` is set or the URL doesn't parse.
+    pub async fn is_allowed(&self, url: &str) -> bool {
+        if self.ignore_robots {
+            return true;
+        }
+        let Ok(parsed) = Url::parse(url) else {
+            return true;
+        };
+        self.rules_for(&parsed).await.allows(parsed.path())
+    }
+
+    /// Blocks until it's this host's turn, honoring the larger of robots.txt's
+    /// `Crawl-delay` and the configured `--rate-limit`.
+    pub async fn wait_turn(&self, url: &str) {
+        let Ok(parsed) = Url::parse(url) else {
+            return;
+        };
+        let host = parsed.host_str().unwrap_or("").to_string();
+
+        let delay = if self.ignore_robots {
+            self.rate_limit
+        } else {
+            let crawl_delay = self.rules_for(&parsed).await.crawl_delay.unwrap_or(Duration::ZERO);
+            crawl_delay.max(self.rate_limit)
+        };
+
+        if delay.is_zero() {
+            return;
+        }
+
+        // Reserve this host's next slot while holding the lock just long
+        // enough to read and update it, then sleep outside the lock so a
+        // wait for one host doesn't block every other host's fetches.
+        // Chain off the previously reserved slot (not `now`) so concurrent
+        // callers for the same host queue up one `delay` apart instead of
+        // all landing on the same slot.
+        let wait = {
+            let mut last_request_at = self.last_request_at.lock().await;
+            let now = Instant::now();
+            let slot = match last_request_at.get(&host) {
+                Some(&reserved) if reserved > now => reserved,
+                _ => now,
+            };
+            last_request_at.insert(host, slot + delay);
+            slot - now
+        };
+
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
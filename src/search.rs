@@ -1,147 +1,286 @@
 // src/search.rs
 
 use async_stream::stream;
-use futures::Stream;
-use reqwest::{Client};
+use futures::stream::FuturesUnordered;
+use futures::{Stream, StreamExt};
+use reqwest::Client;
 use scraper::{Html, Selector};
+use std::collections::HashSet;
+use std::sync::OnceLock;
 use tokio::sync::mpsc::UnboundedSender;
 use urlencoding::encode;
 use regex::Regex;
 
-/// Performs a site-specific internet search and sends found URLs to the crawler via the provided sender.
+/// The pooled `Client` used for every search request.
 ///
-/// # Arguments
+/// Kept deliberately separate from the crawler's shared, user-configurable
+/// `Client`: search engines are liable to block or degrade results for
+/// non-browser/non-Googlebot user agents, so this always identifies as
+/// Googlebot regardless of `--user-agent`. Built once and reused so search
+/// traffic still pools connections instead of paying TCP+TLS setup per call.
+fn search_client() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        Client::builder()
+            .user_agent("Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)")
+            .build()
+            .expect("Failed to build HTTP client")
+    })
+}
+
+/// A pluggable search backend.
 ///
-/// * `search_site` - The site to search (e.g., "example.com").
-/// * `search_limit` - Maximum number of search results to retrieve.
-/// * `url_sender` - Sender to send found URLs to the crawler.
-pub async fn search_site_urls(
-    search_site: &str,
-    search_limit: u32,
-    url_sender: UnboundedSender<String>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Build the HTTP client with a user agent
-    let client = Client::builder()
-        .user_agent("Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)")
-        .build()?;
+/// Knows how to build a paginated `site:` query URL for itself and how to
+/// pull result links out of the HTML that query returns, so a single engine
+/// breaking its markup doesn't take down search entirely.
+pub trait SearchEngine: Send + Sync {
+    /// Name used for CLI selection and logging (e.g. "google", "bing").
+    fn name(&self) -> &'static str;
 
-    let mut results_fetched = 0;
-    let mut start_index = 0;
+    /// Builds the URL for the `start`'th result of a `site:site` search.
+    fn build_query_url(&self, site: &str, start: u32) -> String;
 
-    while results_fetched < search_limit {
-        let search_query = format!("site:{}", search_site);
-        let url = format!(
-            "https://www.google.com/search?q={}&start={}",
-            encode(&search_query),
-            start_index
-        );
+    /// Extracts result URLs from a parsed results page.
+    fn parse_results(&self, html: &Html) -> Vec<String>;
 
-        // Fetch the search results page
-        let resp = client.get(&url).send().await?;
-        let body = resp.text().await?;
+    /// Number of results returned per page, used to advance `start`.
+    fn page_size(&self) -> u32 {
+        10
+    }
+}
+
+/// Scrapes Google's `/url?q=...&sa=` redirect links.
+pub struct GoogleEngine;
+
+/// Scrapes Bing's `b_algo` result headings.
+pub struct BingEngine;
+
+/// Scrapes the no-JS DuckDuckGo HTML endpoint.
+pub struct DuckDuckGoEngine;
 
-        // Parse the HTML to extract result links
-        let document = Html::parse_document(&body);
+/// Scrapes Brave's result snippets.
+pub struct BraveEngine;
+
+impl SearchEngine for GoogleEngine {
+    fn name(&self) -> &'static str {
+        "google"
+    }
+
+    fn build_query_url(&self, site: &str, start: u32) -> String {
+        format!(
+            "https://www.google.com/search?q={}&start={}",
+            encode(&format!("site:{}", site)),
+            start
+        )
+    }
+
+    fn parse_results(&self, html: &Html) -> Vec<String> {
         let selector = Selector::parse("a").unwrap();
-        let links = document.select(&selector);
-        let links: Vec<_> = links
-            .map(|link| link.value().attr("href"))
-            .filter_map(|opt| opt)
-            .collect();
         let pattern = Regex::new(r"/url\?q=(.*?)&sa=").unwrap();
-        let mut found_urls = Vec::new();
+        html.select(&selector)
+            .filter_map(|link| link.value().attr("href"))
+            .filter_map(|href| pattern.captures(href))
+            .filter_map(|caps| caps.get(1).map(|m| m.as_str().to_string()))
+            .collect()
+    }
+}
 
-        for link in links {
-            if let Some(caps) = pattern.captures(&link) {
-                if let Some(url_match) = caps.get(1) {
-                    let url = url_match.as_str();
-                    found_urls.push(url.to_string());
-                }
-            }
-        }
+impl SearchEngine for BingEngine {
+    fn name(&self) -> &'static str {
+        "bing"
+    }
 
-        if found_urls.is_empty() {
-            // No more results
-            break;
-        }
+    fn build_query_url(&self, site: &str, start: u32) -> String {
+        format!(
+            "https://www.bing.com/search?q={}&first={}",
+            encode(&format!("site:{}", site)),
+            start + 1
+        )
+    }
 
-        for link in found_urls {
-            if results_fetched >= search_limit {
-                break;
-            }
-            url_sender
-                .send(link.clone())
-                .unwrap_or_else(|err| eprintln!("Error sending URL from search: {}", err));
-            results_fetched += 1;
-        }
+    fn parse_results(&self, html: &Html) -> Vec<String> {
+        let selector = Selector::parse("li.b_algo h2 a[href]").unwrap();
+        html.select(&selector)
+            .filter_map(|link| link.value().attr("href"))
+            .map(|href| href.to_string())
+            .collect()
+    }
+}
+
+impl SearchEngine for DuckDuckGoEngine {
+    fn name(&self) -> &'static str {
+        "duckduckgo"
+    }
+
+    fn build_query_url(&self, site: &str, start: u32) -> String {
+        format!(
+            "https://html.duckduckgo.com/html/?q={}&s={}",
+            encode(&format!("site:{}", site)),
+            start
+        )
+    }
+
+    fn parse_results(&self, html: &Html) -> Vec<String> {
+        let selector = Selector::parse("a.result__a[href]").unwrap();
+        html.select(&selector)
+            .filter_map(|link| link.value().attr("href"))
+            .map(|href| href.to_string())
+            .collect()
+    }
+}
+
+impl SearchEngine for BraveEngine {
+    fn name(&self) -> &'static str {
+        "brave"
+    }
+
+    fn build_query_url(&self, site: &str, start: u32) -> String {
+        format!(
+            "https://search.brave.com/search?q={}&offset={}",
+            encode(&format!("site:{}", site)),
+            start / self.page_size()
+        )
+    }
 
-        start_index += 10; // Assuming each page has 10 results
+    fn parse_results(&self, html: &Html) -> Vec<String> {
+        let selector = Selector::parse("div.snippet a[href]").unwrap();
+        html.select(&selector)
+            .filter_map(|link| link.value().attr("href"))
+            .map(|href| href.to_string())
+            .collect()
+    }
+}
+
+/// Resolves a `--search-engine` CLI value into its `SearchEngine` impl.
+///
+/// Falls back to Google (with a warning on stderr) for unrecognized names so
+/// a typo doesn't silently drop the whole search.
+pub fn engine_from_name(name: &str) -> Box<dyn SearchEngine> {
+    match name.to_ascii_lowercase().as_str() {
+        "bing" => Box::new(BingEngine),
+        "duckduckgo" | "ddg" => Box::new(DuckDuckGoEngine),
+        "brave" => Box::new(BraveEngine),
+        "google" => Box::new(GoogleEngine),
+        other => {
+            eprintln!("Unknown search engine '{}', falling back to google", other);
+            Box::new(GoogleEngine)
+        }
     }
+}
+
+/// One engine's position in its own result pagination.
+struct EngineCursor {
+    engine: Box<dyn SearchEngine>,
+    start: u32,
+}
+
+/// Fetches and parses a single page of results for one engine's cursor.
+async fn fetch_page(
+    client: Client,
+    search_site: String,
+    cursor: EngineCursor,
+) -> (EngineCursor, Vec<String>) {
+    let url = cursor.engine.build_query_url(&search_site, cursor.start);
 
+    let urls = match client.get(&url).send().await {
+        Ok(resp) => match resp.text().await {
+            Ok(body) => cursor.engine.parse_results(&Html::parse_document(&body)),
+            Err(_) => vec![],
+        },
+        Err(_) => vec![],
+    };
+
+    (cursor, urls)
+}
+
+/// Performs a site-specific internet search across one or more engines and
+/// sends found URLs to the crawler via the provided sender.
+///
+/// # Arguments
+///
+/// * `engines` - The search backends to fan out across.
+/// * `search_site` - The site to search (e.g., "example.com").
+/// * `search_limit` - Maximum number of search results to retrieve in total.
+/// * `url_sender` - Sender to send found URLs to the crawler.
+pub async fn search_site_urls(
+    engines: Vec<Box<dyn SearchEngine>>,
+    search_site: &str,
+    search_limit: u32,
+    url_sender: UnboundedSender<(String, usize)>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut stream = Box::pin(search_site_urls_stream(engines, search_site, search_limit));
+    while let Some(url) = stream.next().await {
+        // Search results are seeds, so they start the crawl at depth 0.
+        url_sender
+            .send((url, 0))
+            .unwrap_or_else(|err| eprintln!("Error sending URL from search: {}", err));
+    }
     Ok(())
 }
 
-/// Performs a site-specific internet search and returns a stream of found URLs.
+/// Performs a site-specific internet search across one or more engines and
+/// returns a deduplicated stream of found URLs.
+///
+/// Each engine is paginated independently and fanned out across a shared
+/// `FuturesUnordered`, so one engine stalling (or running dry) doesn't block
+/// the others. Results already yielded by one engine are suppressed if a
+/// later engine finds the same URL.
 ///
 /// # Arguments
 ///
+/// * `engines` - The search backends to fan out across.
 /// * `search_site` - The site to search (e.g., "example.com").
-/// * `search_limit` - Maximum number of search results to retrieve.
+/// * `search_limit` - Maximum number of search results to retrieve in total.
 pub fn search_site_urls_stream(
+    engines: Vec<Box<dyn SearchEngine>>,
     search_site: &str,
     search_limit: u32,
 ) -> impl Stream<Item = String> {
     let search_site = search_site.to_string();
-    let client = Client::builder()
-        .user_agent("Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)")
-        .build()
-        .expect("Failed to build HTTP client");
+    let client = search_client().clone();
 
     stream! {
-        let mut results_fetched = 0;
-        let mut start_index = 0;
+        let mut seen = HashSet::new();
+        let mut pending = FuturesUnordered::new();
 
+        for engine in engines {
+            let cursor = EngineCursor { engine, start: 0 };
+            pending.push(fetch_page(client.clone(), search_site.clone(), cursor));
+        }
+
+        let mut results_fetched = 0;
         while results_fetched < search_limit {
-            let search_query = format!("site:{}", search_site);
-            let url = format!(
-                "https://www.google.com/search?q={}&start={}",
-                encode(&search_query),
-                start_index
-            );
-
-            // Fetch the search results page
-            let resp = client.get(&url).send().await;
-            if let Ok(resp) = resp {
-                if let Ok(body) = resp.text().await {
-                    // Parse the HTML to extract result links
-                    let document = Html::parse_document(&body);
-                    let selector = Selector::parse("a").unwrap();
-                    let links = document.select(&selector);
-                    let links: Vec<_> = links
-                        .map(|link| link.value().attr("href"))
-                        .filter_map(|opt| opt)
-                        .collect();
-                    let pattern = Regex::new(r"/url\?q=(.*?)&sa=").unwrap();
-
-                    for link in links {
-                        if let Some(caps) = pattern.captures(&link) {
-                            if let Some(url_match) = caps.get(1) {
-                                let url = url_match.as_str();
-                                yield url.to_string();
-                                results_fetched += 1;
-                                if results_fetched >= search_limit {
-                                    break;
-                                }
-                            }
-                        }
+            let Some((cursor, urls)) = pending.next().await else {
+                break;
+            };
+
+            if urls.is_empty() {
+                // This engine's pagination is exhausted; drop its cursor.
+                continue;
+            }
+
+            for url in &urls {
+                if seen.insert(url.clone()) {
+                    yield url.clone();
+                    results_fetched += 1;
+                    if results_fetched >= search_limit {
+                        break;
                     }
                 }
             }
 
-            if results_fetched >= search_limit {
-                break;
+            if results_fetched < search_limit {
+                let page_size = cursor.engine.page_size();
+                let EngineCursor { engine, start } = cursor;
+                pending.push(fetch_page(
+                    client.clone(),
+                    search_site.clone(),
+                    EngineCursor {
+                        engine,
+                        start: start + page_size,
+                    },
+                ));
             }
-            start_index += 10; // Assuming each page has 10 results
         }
     }
-}
\ No newline at end of file
+}